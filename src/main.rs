@@ -9,25 +9,200 @@ const NUM_FRAMES: usize = 128; // Originalmente 256 (para obter o resultado de c
 const MEM_SIZE: usize = PAGE_SIZE * NUM_FRAMES;
 const TLB_ENTRIES: usize = 16;
 
+// Número da página (8 bits) dividido em índice de diretório e índice de tabela.
+// Parametrizado para que o espaço de endereçamento possa crescer sem precisar
+// materializar todas as tabelas.
+const DIR_BITS: u32 = 4;
+const TABLE_BITS: u32 = 4;
+const DIR_ENTRIES: usize = 1 << DIR_BITS;
+const TABLE_ENTRIES: usize = 1 << TABLE_BITS;
+
+const _: () = assert!(DIR_ENTRIES * TABLE_ENTRIES == NUM_PAGES);
+
+// Política de proteção aplicada às páginas carregadas por uma falta de página,
+// na ausência de informação mais específica sobre a permissão desejada
+const DEFAULT_PROTECTION: Protection = Protection(Protection::READ.0 | Protection::WRITE.0);
+
 const BACKING_STORE_PATH: &str = "BACKING_STORE.bin";
 
-/// Estrutura que representa uma memória
+/// Estrutura que representa uma memória. `data` é esparsa: cada frame só é
+/// alocado e zerado na primeira vez em que é lido ou escrito, então o uso de
+/// memória acompanha o conjunto residente em vez de reservar `MEM_SIZE` de
+/// uma vez
 struct Memory {
-    data: [u8; MEM_SIZE],
-    page_table: PageTable,
+    data: Vec<Option<Box<[u8; PAGE_SIZE]>>>,
+    page_dir: PageDirectory,
     tlb: VecDeque<Entry>,
 }
 
-/// Estrutura usada para mapear uma página a um frame
+/// Estrutura usada para mapear uma página a um frame, junto da proteção em
+/// vigor no momento em que o mapeamento foi inserido
 struct Entry {
     pg_num: u32,
     frame_num: u32,
+    protection: Protection,
 }
 
-/// Estrutura que representa uma tabela de páginas
-struct PageTable {
-    frame_nums: [Option<u32>; NUM_PAGES],
+/// Bits de permissão de acesso de uma página
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Protection(u8);
+
+impl Protection {
+    const READ: Protection = Protection(0b001);
+    const WRITE: Protection = Protection(0b010);
+    const EXEC: Protection = Protection(0b100);
+
+    /// Verifica se todos os bits de `other` estão presentes nesta proteção
+    fn allows(self, other: Protection) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Interpreta uma string como "r", "rw" ou "rx" como um conjunto de flags
+    /// de proteção, usada pelo comando `protect` do driver
+    fn parse(flags: &str) -> Protection {
+        let mut protection = Protection(0);
+
+        for flag in flags.chars() {
+            protection = protection | match flag {
+                'r' => Protection::READ,
+                'w' => Protection::WRITE,
+                'x' => Protection::EXEC,
+                _ => panic!("Flag de proteção inválida: {}", flag),
+            };
+        }
+
+        protection
+    }
+}
+
+impl std::ops::BitOr for Protection {
+    type Output = Protection;
+
+    fn bitor(self, rhs: Protection) -> Protection {
+        Protection(self.0 | rhs.0)
+    }
+}
+
+/// Tipo de acesso realizado sobre um endereço virtual, usado para checar a
+/// proteção da página contra a operação pedida
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AccessKind {
+    Read,
+    Write,
+    Execute,
+}
+
+impl AccessKind {
+    /// Bit de proteção que precisa estar presente na página para permitir este acesso
+    fn required_protection(self) -> Protection {
+        match self {
+            AccessKind::Read => Protection::READ,
+            AccessKind::Write => Protection::WRITE,
+            AccessKind::Execute => Protection::EXEC,
+        }
+    }
+}
+
+/// Estrutura que representa o diretório de páginas (primeiro nível da tabela de páginas).
+/// Cada entrada aponta para uma tabela de segundo nível, alocada sob demanda no
+/// primeiro acesso à página correspondente.
+struct PageDirectory {
+    tables: [Option<Box<PageTable>>; DIR_ENTRIES],
     swap_queue: VecDeque<Entry>,
+    frame_allocator: FrameAllocator,
+}
+
+/// Aloca e recicla números de frame físico: frames liberados entram numa
+/// pilha e são reaproveitados antes de avançar sobre frames nunca usados
+struct FrameAllocator {
+    free_frames: Vec<usize>,
+    next_frame: usize,
+}
+
+impl FrameAllocator {
+    /// Inicializa um alocador sem nenhum frame em uso
+    pub fn new() -> FrameAllocator {
+        FrameAllocator {
+            free_frames: Vec::new(),
+            next_frame: 0,
+        }
+    }
+
+    /// Aloca um frame, preferindo frames reciclados por `dealloc` a frames
+    /// ainda não utilizados
+    pub fn alloc(&mut self) -> Option<usize> {
+        if let Some(frame_num) = self.free_frames.pop() {
+            return Some(frame_num);
+        }
+
+        if self.next_frame < NUM_FRAMES {
+            let frame_num = self.next_frame * PAGE_SIZE;
+            self.next_frame += 1;
+            return Some(frame_num);
+        }
+
+        None
+    }
+
+    /// Devolve o frame `frame_num` ao conjunto de frames livres, para que
+    /// possa ser reaproveitado fora de ordem por uma alocação futura
+    pub fn dealloc(&mut self, frame_num: usize) {
+        self.free_frames.push(frame_num);
+    }
+
+    /// Quantidade de frames atualmente livres para reaproveitamento
+    pub fn free_count(&self) -> usize {
+        self.free_frames.len()
+    }
+}
+
+/// Entrada de uma tabela de páginas de segundo nível: o frame mapeado, a
+/// proteção com a qual a página foi carregada e se ela foi escrita desde que
+/// entrou em memória (bit de dirty, usado para decidir o write-back na troca)
+#[derive(Clone, Copy)]
+struct PageTableEntry {
+    frame_num: u32,
+    protection: Protection,
+    dirty: bool,
+}
+
+/// Estrutura que representa uma tabela de páginas (segundo nível)
+struct PageTable {
+    frame_nums: [Option<PageTableEntry>; TABLE_ENTRIES],
+}
+
+/// Página removida da memória por `PageDirectory::get_frame_num`, junto da
+/// informação necessária para decidir (e executar) o write-back no backing store
+struct EvictedPage {
+    pg_num: u32,
+    frame_num: usize,
+    dirty: bool,
+}
+
+/// Resultado da atribuição de um frame a uma página que sofreu falta: o frame
+/// escolhido e, se a troca expulsou outra página residente, os dados dela
+struct FrameAssignment {
+    frame_num: usize,
+    evicted: Option<EvictedPage>,
+}
+
+/// Nível da tabela de páginas que resolveu uma tradução. Hoje toda página
+/// residente é resolvida na tabela de segundo nível; a variante `Directory`
+/// fica reservada para uma futura entrada de página grande ("huge page")
+/// satisfeita direto pelo diretório
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TranslationLevel {
+    #[allow(dead_code)]
+    Directory,
+    Table,
+}
+
+/// Resultado de `Memory::translate`: a tradução de um endereço virtual feita
+/// sem nenhum efeito colateral sobre o TLB, os dados ou as estatísticas de acesso
+struct Translation {
+    physical_addr: usize,
+    frame_num: u32,
+    level: TranslationLevel,
 }
 
 /// Estrutura que representa o resultado de uma consulta na memória
@@ -35,32 +210,70 @@ struct QueryResult {
     physical_addr: usize,
     page_fault: bool,
     tlb_hit: bool,
+    protection_fault: bool,
+    dirty_write_back: bool,
     value: i8,
 }
 
 impl Memory {
-    /// Inicializa uma memória
+    /// Inicializa uma memória sem nenhum frame físico materializado
     pub fn new() -> Memory {
         Memory {
-            data: [0; MEM_SIZE],
-            page_table: PageTable::new(),
+            data: vec![None; MEM_SIZE / PAGE_SIZE],
+            page_dir: PageDirectory::new(),
             tlb: VecDeque::with_capacity(TLB_ENTRIES),
         }
     }
 
-    /// Consulta o TLB, retornando o frame correspondente se a página estiver armazenada nele
-    fn consult_tlb(&self, pg_num: u32) -> Option<u32> {
+    /// Garante que o frame `frame_idx` esteja materializado, alocando-o
+    /// zerado na primeira vez em que é acessado
+    fn ensure_frame(&mut self, frame_idx: usize) -> &mut [u8; PAGE_SIZE] {
+        self.data[frame_idx].get_or_insert_with(|| Box::new([0; PAGE_SIZE]))
+    }
+
+    /// Lê o byte no endereço físico `physical_addr`, materializando o frame
+    /// correspondente se necessário
+    fn read_byte(&mut self, physical_addr: usize) -> u8 {
+        let frame_idx = physical_addr / PAGE_SIZE;
+        let offset = physical_addr % PAGE_SIZE;
+        self.ensure_frame(frame_idx)[offset]
+    }
+
+    /// Escreve `value` no endereço físico `physical_addr`, materializando o
+    /// frame correspondente se necessário
+    fn write_byte(&mut self, physical_addr: usize, value: u8) {
+        let frame_idx = physical_addr / PAGE_SIZE;
+        let offset = physical_addr % PAGE_SIZE;
+        self.ensure_frame(frame_idx)[offset] = value;
+    }
+
+    /// Itera sobre os índices dos frames atualmente materializados
+    pub fn allocated_frames(&self) -> impl Iterator<Item = usize> + '_ {
+        self.data.iter()
+            .enumerate()
+            .filter_map(|(frame_idx, frame)| frame.is_some().then_some(frame_idx))
+    }
+
+    /// Quantidade de frames atualmente materializados na memória física esparsa
+    pub fn materialized_frame_count(&self) -> usize {
+        self.data.iter().filter(|frame| frame.is_some()).count()
+    }
+
+    /// Consulta o TLB, retornando o frame e a proteção da página, caso ela
+    /// esteja armazenada nele
+    fn consult_tlb(&self, pg_num: u32) -> Option<(u32, Protection)> {
         for tlb_entry in self.tlb.iter() {
             if tlb_entry.pg_num == pg_num {
-                return Some(tlb_entry.frame_num);
+                return Some((tlb_entry.frame_num, tlb_entry.protection));
             }
         }
 
         None
     }
 
-    /// Insere um mapeamento página-frame no TLB
-    fn update_tlb(&mut self, pg_num: u32, frame_num: u32) {
+    /// Insere um mapeamento página-frame no TLB, junto da proteção da página,
+    /// que passa a ser verificada a partir do próprio TLB em acessos futuros
+    fn update_tlb(&mut self, pg_num: u32, frame_num: u32, protection: Protection) {
         if self.tlb.len() == TLB_ENTRIES {
             // Fila do TLB está cheia, remover página mais antiga (inserida antes)
             self.tlb.pop_front();
@@ -70,100 +283,538 @@ impl Memory {
         self.tlb.push_back(Entry {
             pg_num,
             frame_num,
+            protection,
         });
     }
 
     /// Lê a página `pg_num` do arquivo `bck_store` e a armazena no frame `frame_num`
     fn read_from_file(&mut self, pg_num: u32, frame_num: usize, bck_store: &mut File) {
-        let frame_end = frame_num + PAGE_SIZE;
-
         bck_store.seek(SeekFrom::Start((pg_num * PAGE_SIZE as u32) as u64))
             .expect("Falha ao posicionar cursor no arquivo");
-        bck_store.read(&mut self.data[frame_num..frame_end])
+        bck_store.read_exact(self.ensure_frame(frame_num / PAGE_SIZE))
             .expect("Falha ao ler arquivo");
     }
 
-    /// Consulta a memória usando o endereço virtual `virtual_addr` e o arquivo
-    /// `bck_store` como base
-    pub fn query(&mut self, virtual_addr: u32, bck_store: &mut File) -> QueryResult {
+    /// Escreve o frame `frame_num` de volta na posição da página `pg_num` no
+    /// arquivo `bck_store`; o inverso de `read_from_file`, usado para o
+    /// write-back de páginas dirty na troca
+    fn write_to_file(&self, pg_num: u32, frame_num: usize, bck_store: &mut File) {
+        let frame = self.data[frame_num / PAGE_SIZE].as_ref()
+            .expect("Write-back de um frame que nunca foi materializado");
+
+        bck_store.seek(SeekFrom::Start((pg_num * PAGE_SIZE as u32) as u64))
+            .expect("Falha ao posicionar cursor no arquivo");
+        bck_store.write_all(frame.as_ref())
+            .expect("Falha ao escrever no arquivo");
+    }
+
+    /// Consulta a memória usando o endereço virtual `virtual_addr`, o tipo de
+    /// acesso `access` e o arquivo `bck_store` como base
+    pub fn query(&mut self, virtual_addr: u32, access: AccessKind, bck_store: &mut File) -> QueryResult {
         // Extrair os 8 primeiros bits do endereço (número da página)
         let pg_num = virtual_addr >> 8;
         // Extrair os 8 últimos bits do endereço (deslocamento)
         let offset = (virtual_addr & 0xFF) as usize;
 
-        if let Some(frame_num) = self.consult_tlb(pg_num) {
+        if let Some((frame_num, protection)) = self.consult_tlb(pg_num) {
             // TLB hit
 
+            if !protection.allows(access.required_protection()) {
+                return QueryResult {
+                    physical_addr: frame_num as usize + offset,
+                    page_fault: false,
+                    tlb_hit: true,
+                    protection_fault: true,
+                    dirty_write_back: false,
+                    value: 0,
+                };
+            }
+
             let physical_addr = frame_num as usize + offset;
-                
+
             QueryResult {
                 physical_addr,
                 page_fault: false,
                 tlb_hit: true,
-                value: self.data[physical_addr] as i8,
+                protection_fault: false,
+                dirty_write_back: false,
+                value: self.read_byte(physical_addr) as i8,
             }
-        } else if let Some(frame_num) = self.page_table.frame_nums[pg_num as usize] {
+        } else if let Some(entry) = self.page_dir.query_frame(pg_num) {
             // Page hit
 
-            self.update_tlb(pg_num, frame_num as u32);
+            self.update_tlb(pg_num, entry.frame_num, entry.protection);
+
+            if !entry.protection.allows(access.required_protection()) {
+                return QueryResult {
+                    physical_addr: entry.frame_num as usize + offset,
+                    page_fault: false,
+                    tlb_hit: false,
+                    protection_fault: true,
+                    dirty_write_back: false,
+                    value: 0,
+                };
+            }
 
-            let physical_addr = frame_num as usize + offset;
+            let physical_addr = entry.frame_num as usize + offset;
 
             QueryResult {
                 physical_addr,
                 page_fault: false,
                 tlb_hit: false,
-                value: self.data[physical_addr] as i8,
+                protection_fault: false,
+                dirty_write_back: false,
+                value: self.read_byte(physical_addr) as i8,
             }
         } else {
-            // Page miss
+            // Page miss (falta de diretório ou de tabela)
+
+            let (frame_num, dirty_write_back) = self.load_page(pg_num, DEFAULT_PROTECTION, bck_store);
+
+            if !DEFAULT_PROTECTION.allows(access.required_protection()) {
+                return QueryResult {
+                    physical_addr: frame_num + offset,
+                    page_fault: true,
+                    tlb_hit: false,
+                    protection_fault: true,
+                    dirty_write_back,
+                    value: 0,
+                };
+            }
+
+            let physical_addr = frame_num + offset;
+
+            QueryResult {
+                physical_addr,
+                page_fault: true,
+                tlb_hit: false,
+                protection_fault: false,
+                dirty_write_back,
+                value: self.read_byte(physical_addr) as i8,
+            }
+        }
+    }
+
+    /// Escreve `value` no endereço virtual `virtual_addr`, marcando a página
+    /// correspondente como dirty para que seja escrita de volta no backing
+    /// store quando, eventualmente, for removida da memória
+    pub fn store(&mut self, virtual_addr: u32, value: i8, bck_store: &mut File) -> QueryResult {
+        let pg_num = virtual_addr >> 8;
+        let offset = (virtual_addr & 0xFF) as usize;
+
+        if let Some((frame_num, protection)) = self.consult_tlb(pg_num) {
+            // TLB hit
+
+            if !protection.allows(AccessKind::Write.required_protection()) {
+                return QueryResult {
+                    physical_addr: frame_num as usize + offset,
+                    page_fault: false,
+                    tlb_hit: true,
+                    protection_fault: true,
+                    dirty_write_back: false,
+                    value: 0,
+                };
+            }
+
+            let physical_addr = frame_num as usize + offset;
+            self.write_byte(physical_addr, value as u8);
+            self.page_dir.mark_dirty(pg_num);
+
+            QueryResult {
+                physical_addr,
+                page_fault: false,
+                tlb_hit: true,
+                protection_fault: false,
+                dirty_write_back: false,
+                value,
+            }
+        } else if let Some(entry) = self.page_dir.query_frame(pg_num) {
+            // Page hit
+
+            self.update_tlb(pg_num, entry.frame_num, entry.protection);
+
+            if !entry.protection.allows(AccessKind::Write.required_protection()) {
+                return QueryResult {
+                    physical_addr: entry.frame_num as usize + offset,
+                    page_fault: false,
+                    tlb_hit: false,
+                    protection_fault: true,
+                    dirty_write_back: false,
+                    value: 0,
+                };
+            }
 
-            let frame_num = self.page_table.get_frame_num(pg_num);
+            let physical_addr = entry.frame_num as usize + offset;
+            self.write_byte(physical_addr, value as u8);
+            self.page_dir.mark_dirty(pg_num);
 
-            self.update_tlb(pg_num, frame_num as u32);
-            self.read_from_file(pg_num, frame_num, bck_store);
+            QueryResult {
+                physical_addr,
+                page_fault: false,
+                tlb_hit: false,
+                protection_fault: false,
+                dirty_write_back: false,
+                value,
+            }
+        } else {
+            // Page miss (falta de diretório ou de tabela)
+
+            let (frame_num, dirty_write_back) = self.load_page(pg_num, DEFAULT_PROTECTION, bck_store);
+
+            if !DEFAULT_PROTECTION.allows(AccessKind::Write.required_protection()) {
+                return QueryResult {
+                    physical_addr: frame_num + offset,
+                    page_fault: true,
+                    tlb_hit: false,
+                    protection_fault: true,
+                    dirty_write_back,
+                    value: 0,
+                };
+            }
 
             let physical_addr = frame_num + offset;
-            
+            self.write_byte(physical_addr, value as u8);
+            self.page_dir.mark_dirty(pg_num);
+
             QueryResult {
                 physical_addr,
                 page_fault: true,
                 tlb_hit: false,
-                value: self.data[physical_addr] as i8,
+                protection_fault: false,
+                dirty_write_back,
+                value,
             }
         }
     }
+
+    /// Trata uma falta de página: obtém um frame para `pg_num` (fazendo
+    /// write-back da página expulsa se ela estiver dirty), atualiza o TLB e
+    /// carrega o conteúdo da página a partir do backing store. Retorna o
+    /// frame atribuído e se um write-back foi executado
+    fn load_page(&mut self, pg_num: u32, protection: Protection, bck_store: &mut File) -> (usize, bool) {
+        let assignment = self.page_dir.get_frame_num(pg_num, protection);
+
+        let mut dirty_write_back = false;
+        if let Some(evicted) = assignment.evicted {
+            if evicted.dirty {
+                self.write_to_file(evicted.pg_num, evicted.frame_num, bck_store);
+                dirty_write_back = true;
+            }
+        }
+
+        self.update_tlb(pg_num, assignment.frame_num as u32, protection);
+        self.read_from_file(pg_num, assignment.frame_num, bck_store);
+
+        (assignment.frame_num, dirty_write_back)
+    }
+
+    /// Altera a proteção da página que contém `virtual_addr`, carregando-a do
+    /// backing store primeiro caso ainda não esteja residente. Qualquer
+    /// entrada do TLB para a página é removida, já que ela pode ter sido
+    /// inserida com a proteção antiga. Retorna se carregar a página expulsou
+    /// uma outra que precisou de write-back, para que o chamador contabilize
+    pub fn set_protection(&mut self, virtual_addr: u32, protection: Protection, bck_store: &mut File) -> bool {
+        let pg_num = virtual_addr >> 8;
+
+        let mut dirty_write_back = false;
+        if self.page_dir.query_frame(pg_num).is_none() {
+            (_, dirty_write_back) = self.load_page(pg_num, protection, bck_store);
+        }
+
+        self.page_dir.set_protection(pg_num, protection);
+        self.tlb.retain(|entry| entry.pg_num != pg_num);
+
+        dirty_write_back
+    }
+
+    /// Percorre a tabela de páginas para `virtual_addr` sem tocar no TLB, sem
+    /// ler `self.data` e sem contabilizar faltas de página ou de TLB.
+    /// Retorna `None` quando a página ainda não está residente
+    pub fn translate(&self, virtual_addr: u32) -> Option<Translation> {
+        let pg_num = virtual_addr >> 8;
+        let offset = (virtual_addr & 0xFF) as usize;
+
+        let entry = self.page_dir.query_frame(pg_num)?;
+
+        Some(Translation {
+            physical_addr: entry.frame_num as usize + offset,
+            frame_num: entry.frame_num,
+            level: TranslationLevel::Table,
+        })
+    }
+}
+
+impl PageDirectory {
+    /// Inicializa um diretório de páginas vazio
+    pub fn new() -> PageDirectory {
+        PageDirectory {
+            tables: std::array::from_fn(|_| None),
+            swap_queue: VecDeque::with_capacity(NUM_FRAMES),
+            frame_allocator: FrameAllocator::new(),
+        }
+    }
+
+    /// Quantidade de frames atualmente livres para reaproveitamento
+    pub fn free_frame_count(&self) -> usize {
+        self.frame_allocator.free_count()
+    }
+
+    /// Índice da entrada de diretório correspondente a `pg_num`
+    fn dir_index(pg_num: u32) -> usize {
+        (pg_num >> TABLE_BITS) as usize
+    }
+
+    /// Índice da entrada de tabela correspondente a `pg_num`
+    fn table_index(pg_num: u32) -> usize {
+        (pg_num & (TABLE_ENTRIES as u32 - 1)) as usize
+    }
+
+    /// Consulta o frame e a proteção mapeados para `pg_num` sem alocar nada;
+    /// `None` caso o diretório ou a tabela de segundo nível ainda não existam
+    pub fn query_frame(&self, pg_num: u32) -> Option<PageTableEntry> {
+        self.tables[Self::dir_index(pg_num)]
+            .as_ref()
+            .and_then(|table| table.frame_nums[Self::table_index(pg_num)])
+    }
+
+    /// Marca a página `pg_num` como dirty, caso ela esteja residente; usada
+    /// por toda escrita de `Memory::store`, seja ela atendida pelo TLB, por um
+    /// hit na tabela ou após uma falta de página
+    fn mark_dirty(&mut self, pg_num: u32) {
+        if let Some(table) = self.tables[Self::dir_index(pg_num)].as_mut() {
+            if let Some(entry) = table.frame_nums[Self::table_index(pg_num)].as_mut() {
+                entry.dirty = true;
+            }
+        }
+    }
+
+    /// Altera a proteção da página `pg_num`, caso ela esteja residente;
+    /// usada pelo comando `protect` do driver para restringir ou liberar
+    /// acessos a uma página já carregada
+    fn set_protection(&mut self, pg_num: u32, protection: Protection) {
+        if let Some(table) = self.tables[Self::dir_index(pg_num)].as_mut() {
+            if let Some(entry) = table.frame_nums[Self::table_index(pg_num)].as_mut() {
+                entry.protection = protection;
+            }
+        }
+    }
+
+    /// Obtém o número do frame correspondente à página `pg_num`, alocando a
+    /// tabela de segundo nível sob demanda caso ainda não exista e marcando a
+    /// página com a proteção `protection`. Se a troca expulsar uma página
+    /// residente, a informação dela (inclusive se está dirty) é devolvida
+    /// para que o chamador decida sobre o write-back
+    pub fn get_frame_num(&mut self, pg_num: u32, protection: Protection) -> FrameAssignment {
+        let mut evicted = None;
+
+        if self.swap_queue.len() == NUM_FRAMES {
+            // Memória está cheia, remover página mais antiga e devolver o seu frame ao alocador
+            let swapped_page = self.swap_queue.pop_front().unwrap();
+            let dir_idx = Self::dir_index(swapped_page.pg_num);
+            let tbl_idx = Self::table_index(swapped_page.pg_num);
+            let dirty = self.tables[dir_idx]
+                .as_mut()
+                .and_then(|table| table.frame_nums[tbl_idx].take())
+                .is_some_and(|entry| entry.dirty);
+
+            self.frame_allocator.dealloc(swapped_page.frame_num as usize);
+            evicted = Some(EvictedPage {
+                pg_num: swapped_page.pg_num,
+                frame_num: swapped_page.frame_num as usize,
+                dirty,
+            });
+        }
+
+        let frame_num = self.frame_allocator.alloc()
+            .expect("Alocador de frames sem frames livres apesar da fila de swap ter espaço");
+
+        self.swap_queue.push_back(Entry { pg_num, frame_num: frame_num as u32, protection });
+
+        let table = self.tables[Self::dir_index(pg_num)]
+            .get_or_insert_with(|| Box::new(PageTable::new()));
+        table.frame_nums[Self::table_index(pg_num)] = Some(PageTableEntry {
+            frame_num: frame_num as u32,
+            protection,
+            dirty: false,
+        });
+
+        FrameAssignment { frame_num, evicted }
+    }
 }
 
 impl PageTable {
-    /// Inicializa uma tabela de páginas
+    /// Inicializa uma tabela de páginas de segundo nível
     pub fn new() -> PageTable {
         PageTable {
-            frame_nums: [None; NUM_PAGES],
-            swap_queue: VecDeque::with_capacity(NUM_FRAMES),
+            frame_nums: [None; TABLE_ENTRIES],
         }
     }
+}
 
-    /// Obtém o número do frame correspondente à página `pg_num`
-    pub fn get_frame_num(&mut self, pg_num: u32) -> usize {
-        let frame_num = if self.swap_queue.len() == NUM_FRAMES {
-            // Memória está cheia, remover página mais antiga e usar o seu frame
-            let swapped_page = self.swap_queue.pop_front().unwrap();
-            self.frame_nums[swapped_page.pg_num as usize] = None;
-            swapped_page.frame_num as usize
+// Tamanho do buffer de staging usado pelo `BlockCopier`; uma página inteira por vez
+const COPY_BUF_SIZE: usize = PAGE_SIZE;
+
+/// Estatísticas acumuladas por um passo do `BlockCopier`, somadas aos
+/// contadores globais de `main` conforme a cópia avança
+#[derive(Default)]
+struct CopyStats {
+    translated: i32,
+    page_faults: i32,
+    tlb_hits: i32,
+    protection_faults: i32,
+    dirty_write_backs: i32,
+}
+
+impl CopyStats {
+    fn add_query(&mut self, result: &QueryResult) {
+        self.translated += 1;
+
+        if result.page_fault {
+            self.page_faults += 1;
+        }
+
+        if result.tlb_hit {
+            self.tlb_hits += 1;
+        }
+
+        if result.protection_fault {
+            self.protection_faults += 1;
+        }
+
+        if result.dirty_write_back {
+            self.dirty_write_backs += 1;
+        }
+    }
+}
+
+/// Operação de cópia de blocos que avança página a página entre dois
+/// endereços virtuais. Cada passo copia através de um buffer intermediário,
+/// o que mantém a cópia correta mesmo quando as regiões de `src` e `dst` se
+/// sobrepõem
+struct BlockCopier {
+    // Quando `backward` é falso, `cursor_src`/`cursor_dst` apontam para o
+    // próximo byte a copiar e avançam a cada passo. Quando é verdadeiro, eles
+    // apontam para um byte além do último byte restante e retrocedem a cada
+    // passo, para que a cópia avance de trás para frente (como `memmove`)
+    cursor_src: u32,
+    cursor_dst: u32,
+    remaining: usize,
+    backward: bool,
+    buf: [u8; COPY_BUF_SIZE],
+}
+
+impl BlockCopier {
+    /// Inicia uma cópia de `count` bytes do endereço virtual `src` para `dst`.
+    /// Quando as regiões se sobrepõem e `dst` vem depois de `src`, uma cópia
+    /// direta de baixo para cima sobrescreveria bytes de `src` antes de serem
+    /// lidos; nesse caso a cópia é feita de trás para frente, como `memmove`
+    pub fn new(src: u32, dst: u32, count: usize) -> BlockCopier {
+        let overlaps_forward = dst > src && (dst - src) < count as u32;
+
+        if overlaps_forward {
+            BlockCopier {
+                cursor_src: src + count as u32,
+                cursor_dst: dst + count as u32,
+                remaining: count,
+                backward: true,
+                buf: [0; COPY_BUF_SIZE],
+            }
+        } else {
+            BlockCopier {
+                cursor_src: src,
+                cursor_dst: dst,
+                remaining: count,
+                backward: false,
+                buf: [0; COPY_BUF_SIZE],
+            }
+        }
+    }
+
+    /// Indica se a cópia já transferiu todos os bytes pedidos
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Quantos bytes cabem antes do próximo limite de página, na direção da cópia
+    fn page_run(cursor: u32, backward: bool) -> usize {
+        let offset = (cursor & 0xFF) as usize;
+        if backward {
+            if offset == 0 { PAGE_SIZE } else { offset }
+        } else {
+            PAGE_SIZE - offset
+        }
+    }
+
+    /// Executa um passo da cópia: transfere até o menor limite entre o fim da
+    /// página atual de `src`, o fim da página atual de `dst`, o que resta
+    /// copiar e o tamanho do buffer de staging, acionando a tradução normal de
+    /// `memory` (e suas faltas de página/TLB) para cada byte
+    pub fn step(&mut self, memory: &mut Memory, bck_store: &mut File) -> CopyStats {
+        let mut stats = CopyStats::default();
+
+        let chunk = self.remaining
+            .min(Self::page_run(self.cursor_src, self.backward))
+            .min(Self::page_run(self.cursor_dst, self.backward))
+            .min(COPY_BUF_SIZE);
+
+        let (base_src, base_dst) = if self.backward {
+            (self.cursor_src - chunk as u32, self.cursor_dst - chunk as u32)
         } else {
-            self.swap_queue.len() * PAGE_SIZE
+            (self.cursor_src, self.cursor_dst)
         };
 
-        self.swap_queue.push_back(Entry { pg_num, frame_num: frame_num as u32});
-        self.frame_nums[pg_num as usize] = Some(frame_num as u32);
+        for i in 0..chunk {
+            let result = memory.query((base_src + i as u32) & 0xFFFF, AccessKind::Read, bck_store);
+            stats.add_query(&result);
+            self.buf[i] = result.value as u8;
+        }
+
+        for i in 0..chunk {
+            let result = memory.store((base_dst + i as u32) & 0xFFFF, self.buf[i] as i8, bck_store);
+            stats.add_query(&result);
+        }
+
+        if self.backward {
+            self.cursor_src -= chunk as u32;
+            self.cursor_dst -= chunk as u32;
+        } else {
+            self.cursor_src += chunk as u32;
+            self.cursor_dst += chunk as u32;
+        }
+        self.remaining -= chunk;
+
+        stats
+    }
+}
+
+/// Acumula o resultado de uma consulta nos contadores globais de `main`,
+/// evitando repetir o mesmo bloco de `if`s em cada comando que faz uma consulta
+fn record_query(result: &QueryResult, count: &mut i32, page_faults: &mut i32, tlb_hits: &mut i32, protection_faults: &mut i32, dirty_write_backs: &mut i32) {
+    *count += 1;
+
+    if result.page_fault {
+        *page_faults += 1;
+    }
+
+    if result.tlb_hit {
+        *tlb_hits += 1;
+    }
+
+    if result.protection_fault {
+        *protection_faults += 1;
+    }
 
-        frame_num
+    if result.dirty_write_back {
+        *dirty_write_backs += 1;
     }
 }
 
 fn main() -> std::io::Result<()> {
-    let mut bck_store = File::open(BACKING_STORE_PATH).expect("Arquivo backing store não encontrado");
+    let mut bck_store = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(BACKING_STORE_PATH)
+        .expect("Arquivo backing store não encontrado");
 
     let path = env::args().nth(1).expect("Informe um arquivo");
     let file = File::open(path)?;
@@ -173,6 +824,8 @@ fn main() -> std::io::Result<()> {
 
     let mut page_faults = 0;
     let mut tlb_hits = 0;
+    let mut protection_faults = 0;
+    let mut dirty_write_backs = 0;
     let mut count = 0;
 
     loop {
@@ -184,20 +837,113 @@ fn main() -> std::io::Result<()> {
             break;
         }
 
-        count += 1;
+        let line = addr.trim();
 
-        let addr: u32 = addr.trim().parse().expect("Número inválido");
-        let addr_masked = addr & 0xFFFF;
-        let query_result = memory.query(addr_masked, &mut bck_store);
+        if let Some(args) = line.strip_prefix("copy ") {
+            // Comando de cópia em bloco: "copy src dst count"
+            let mut args = args.split_whitespace();
+            let src: u32 = args.next().expect("Comando copy incompleto")
+                .parse().expect("Número inválido");
+            let dst: u32 = args.next().expect("Comando copy incompleto")
+                .parse().expect("Número inválido");
+            let copy_count: usize = args.next().expect("Comando copy incompleto")
+                .parse().expect("Número inválido");
+
+            let mut copier = BlockCopier::new(src & 0xFFFF, dst & 0xFFFF, copy_count);
+
+            while !copier.is_done() {
+                let stats = copier.step(&mut memory, &mut bck_store);
+
+                count += stats.translated;
+                page_faults += stats.page_faults;
+                tlb_hits += stats.tlb_hits;
+                protection_faults += stats.protection_faults;
+                dirty_write_backs += stats.dirty_write_backs;
+            }
+
+            println!("Copy: {} bytes from {} to {}", copy_count, src & 0xFFFF, dst & 0xFFFF);
+
+            continue;
+        }
+
+        if let Some(args) = line.strip_prefix("protect ") {
+            // Comando de proteção: "protect addr flags", onde flags é uma
+            // combinação de "r", "w" e "x" (ex.: "rx")
+            let mut args = args.split_whitespace();
+            let addr: u32 = args.next().expect("Comando protect incompleto")
+                .parse().expect("Número inválido");
+            let flags = args.next().expect("Comando protect incompleto");
+
+            let addr_masked = addr & 0xFFFF;
+            if memory.set_protection(addr_masked, Protection::parse(flags), &mut bck_store) {
+                dirty_write_backs += 1;
+            }
+
+            println!("Protect: address {} set to \"{}\"", addr_masked, flags);
+
+            continue;
+        }
 
-        if query_result.page_fault {
-            page_faults += 1;
+        if let Some(args) = line.strip_prefix("exec ") {
+            // Comando de acesso de execução: "exec addr", sujeito à checagem
+            // de proteção como qualquer outra consulta
+            let addr: u32 = args.trim().parse().expect("Número inválido");
+            let addr_masked = addr & 0xFFFF;
+            let query_result = memory.query(addr_masked, AccessKind::Execute, &mut bck_store);
+
+            record_query(&query_result, &mut count, &mut page_faults, &mut tlb_hits, &mut protection_faults, &mut dirty_write_backs);
+
+            print!("Virtual address: {} ", addr_masked);
+            print!("Physical address: {} ", query_result.physical_addr);
+            println!("Value: {}", query_result.value);
+
+            continue;
         }
 
-        if query_result.tlb_hit {
-            tlb_hits += 1;
+        if let Some(args) = line.strip_prefix("write ") {
+            // Comando de escrita: "write addr value", exercita o caminho de
+            // store() diretamente em vez de só através do BlockCopier
+            let mut args = args.split_whitespace();
+            let addr: u32 = args.next().expect("Comando write incompleto")
+                .parse().expect("Número inválido");
+            let value: i8 = args.next().expect("Comando write incompleto")
+                .parse().expect("Número inválido");
+
+            let addr_masked = addr & 0xFFFF;
+            let query_result = memory.store(addr_masked, value, &mut bck_store);
+
+            record_query(&query_result, &mut count, &mut page_faults, &mut tlb_hits, &mut protection_faults, &mut dirty_write_backs);
+
+            print!("Virtual address: {} ", addr_masked);
+            print!("Physical address: {} ", query_result.physical_addr);
+            println!("Value: {}", query_result.value);
+
+            continue;
         }
 
+        if let Some(args) = line.strip_prefix("dump ") {
+            // Comando de introspecção: "dump addr", imprime a tradução da
+            // página sem perturbar as estatísticas de hit/falta acumuladas
+            let addr: u32 = args.trim().parse().expect("Número inválido");
+            let addr_masked = addr & 0xFFFF;
+
+            match memory.translate(addr_masked) {
+                Some(translation) => println!(
+                    "Dump: address {} -> physical {} (frame {}, level {:?})",
+                    addr_masked, translation.physical_addr, translation.frame_num, translation.level,
+                ),
+                None => println!("Dump: address {} is not resident", addr_masked),
+            }
+
+            continue;
+        }
+
+        let addr: u32 = line.parse().expect("Número inválido");
+        let addr_masked = addr & 0xFFFF;
+        let query_result = memory.query(addr_masked, AccessKind::Read, &mut bck_store);
+
+        record_query(&query_result, &mut count, &mut page_faults, &mut tlb_hits, &mut protection_faults, &mut dirty_write_backs);
+
         print!("Virtual address: {} ", addr_masked);
         print!("Physical address: {} ", query_result.physical_addr);
         println!("Value: {}", query_result.value);
@@ -208,6 +954,11 @@ fn main() -> std::io::Result<()> {
     println!("Page Fault Rate = {}", page_faults as f64 / count as f64);
     println!("TLB Hits = {}", tlb_hits);
     println!("TLB Hit Rate = {}", tlb_hits as f64 / count as f64);
-    
+    println!("Protection Faults = {}", protection_faults);
+    println!("Dirty Write-Backs = {}", dirty_write_backs);
+    println!("Recycled Free Frames = {}", memory.page_dir.free_frame_count());
+    println!("Materialized Frames = {}", memory.materialized_frame_count());
+    println!("Materialized Frame Indices = {:?}", memory.allocated_frames().collect::<Vec<_>>());
+
     Ok(())
 }